@@ -1,38 +1,36 @@
-use std::fs::File;
+use std::collections::BTreeMap;
 use std::convert::TryFrom;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+
+use clap::Parser;
 
 /// Enumerations corresponding to the characters stored within the file.
 /// They are not meant to be used within the execution process, only parsing.
 #[derive(PartialEq)]
 pub enum Commands {
-    PTR_LEFT,
-    PRT_RIGHT,
-    INCR,
-    DECR,
-    OUTP,
-    INPT,
-    IF_ZERO,
-    JMP_NZERO,
-}
-
-impl Commands {
-    /// Checks whether the command should not begin/close a new code block.
-    const fn not_block(&self) -> bool {
-        self != &Commands::IF_ZERO && self != &Commands::JMP_NZERO
-    }
+    PtrLeft,
+    PtrRight,
+    Incr,
+    Decr,
+    Outp,
+    Inpt,
+    IfZero,
+    JmpNzero,
 }
 
-impl Into<&str> for Commands {
-    fn into(self) -> &'static str {
-        match self {
-            Commands::DECR => "-",
-            Commands::IF_ZERO => "[",
-            Commands::INCR => "+",
-            Commands::INPT => ",",
-            Commands::JMP_NZERO => "]",
-            Commands::OUTP => ".",
-            Commands::PRT_RIGHT => ">",
-            Commands::PTR_LEFT => "<",
+impl From<Commands> for &str {
+    fn from(cmd: Commands) -> Self {
+        match cmd {
+            Commands::Decr => "-",
+            Commands::IfZero => "[",
+            Commands::Incr => "+",
+            Commands::Inpt => ",",
+            Commands::JmpNzero => "]",
+            Commands::Outp => ".",
+            Commands::PtrRight => ">",
+            Commands::PtrLeft => "<",
         }
     }
 }
@@ -42,22 +40,102 @@ impl TryFrom<&str> for Commands {
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
         match value {
-            "-" => Ok(Commands::DECR),
-            "[" => Ok(Commands::IF_ZERO),
-            "+" => Ok(Commands::INCR),
-            "," => Ok(Commands::INPT),
-            "]" => Ok(Commands::JMP_NZERO),
-            "." => Ok(Commands::OUTP),
-            ">" => Ok(Commands::PRT_RIGHT),
-            "<" => Ok(Commands::PTR_LEFT),
+            "-" => Ok(Commands::Decr),
+            "[" => Ok(Commands::IfZero),
+            "+" => Ok(Commands::Incr),
+            "," => Ok(Commands::Inpt),
+            "]" => Ok(Commands::JmpNzero),
+            "." => Ok(Commands::Outp),
+            ">" => Ok(Commands::PtrRight),
+            "<" => Ok(Commands::PtrLeft),
             _   => Err(()),
         }
     }
 }
 
+impl TryFrom<u8> for Commands {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            b'-' => Ok(Commands::Decr),
+            b'[' => Ok(Commands::IfZero),
+            b'+' => Ok(Commands::Incr),
+            b',' => Ok(Commands::Inpt),
+            b']' => Ok(Commands::JmpNzero),
+            b'.' => Ok(Commands::Outp),
+            b'>' => Ok(Commands::PtrRight),
+            b'<' => Ok(Commands::PtrLeft),
+            _    => Err(()),
+        }
+    }
+}
+
 // Changing these allows you to trade mem usage and max cell count.
 type CellType = u8;
-type CellPtrType = CellType;
+
+/// Default number of cells allocated for the tape.
+const DEFAULT_TAPE_SIZE: usize = 30_000;
+
+/// Opt-in execution behaviours that tweak the semantics of the interpreter so
+/// that programs written against other BrainFuck dialects behave as intended.
+#[derive(Clone, Copy, Default, PartialEq)]
+struct Features {
+    /// Arithmetic on a cell wraps modulo `CellType::MAX + 1` instead of being a
+    /// runtime error on over/underflow.
+    cell_wrap: bool,
+    /// Pointer movement past either end of the tape wraps around rather than
+    /// erroring.
+    pointer_wrap: bool,
+}
+
+impl Features {
+    /// Builds a feature set from a list of flag names (e.g. from `--features`),
+    /// returning the offending name on an unknown feature.
+    fn from_names<I, S>(names: I) -> Result<Self, String>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut features = Self::default();
+        for name in names {
+            match name.as_ref() {
+                "cell-wrap" => features.cell_wrap = true,
+                "pointer-wrap" => features.pointer_wrap = true,
+                other => return Err(other.to_string()),
+            }
+        }
+        Ok(features)
+    }
+}
+
+/// Errors that can occur while executing a token sequence.
+enum RuntimeError {
+    Io(io::Error),
+    /// The pointer moved past the end of a non-growing, non-wrapping tape.
+    PointerOverflow,
+    /// The pointer moved before the start of a non-wrapping tape.
+    PointerUnderflow,
+    /// A cell over/underflowed without the `cell-wrap` feature enabled.
+    CellOverflow,
+}
+
+impl From<io::Error> for RuntimeError {
+    fn from(err: io::Error) -> Self {
+        RuntimeError::Io(err)
+    }
+}
+
+impl std::fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuntimeError::Io(err) => write!(f, "{err}"),
+            RuntimeError::PointerOverflow => write!(f, "pointer moved past the end of the tape"),
+            RuntimeError::PointerUnderflow => write!(f, "pointer moved before the start of the tape"),
+            RuntimeError::CellOverflow => write!(f, "cell value over/underflowed"),
+        }
+    }
+}
 
 /// Enums for tokens that will be utilised within execution. These themselves
 /// by design contain some minor execution optimisations.
@@ -65,19 +143,23 @@ type CellPtrType = CellType;
 enum Token {
     Decrement(CellType),
     Increment(CellType),
-    //IfZero,
-    //Input,
-    IfZeroBlock(Vec<Commands>),
-    JumpIfNotZero,
+    Input,
+    IfZeroBlock(Vec<Token>),
     Output,
-    PointerRight(CellPtrType),
-    PointerLeft(CellPtrType),
-}
-
-#[derive(PartialEq)]
-enum ParsingValue {
-    Command(Commands),
-    Block(Vec<ParsingValue>),
+    PointerRight(usize),
+    PointerLeft(usize),
+    /// Writes 0 to the current cell, replacing a `[-]`/`[+]` clear loop.
+    SetZero,
+    /// Adds `current_cell_value * factor` to the cell at `ptr + offset`,
+    /// replacing one accumulation of a multiply/copy loop. `factor` is signed
+    /// so subtract-style copy loops (negative delta) are preserved.
+    MultiplyAdd { offset: isize, factor: i32 },
+    /// Flattened `[`: if the current cell is 0, jump past the matching `]` at
+    /// `target`; otherwise fall through.
+    JumpIfZero { target: usize },
+    /// Flattened `]`: if the current cell is non-zero, jump back to the
+    /// matching `[` at `target`; otherwise fall through.
+    JumpIfNotZero { target: usize },
 }
 
 /// A structure for storing a sequence of tokens.
@@ -86,69 +168,781 @@ struct TokenSequence {
 }
 
 impl TokenSequence {
-    /// Creates an empty token sequence.
-    pub fn new() -> Self {
-        Self {
-            tokens: Vec::new(),
+    /// Parses a slice of commands into a sequence of tokens, folding runs of
+    /// `+ - < >` into the counted token variants and capturing `[...]` blocks
+    /// recursively.
+    ///
+    /// The returned `usize` is the number of commands consumed from the front
+    /// of `cmd_slice`; when called on a block body it stops at the matching
+    /// `]`, allowing the caller to resume after it.
+    fn divide_cmd_slice(cmd_slice: &[Commands]) -> (Vec<Token>, usize) {
+        let mut tokens = Vec::with_capacity(cmd_slice.len() / 2);
+        let mut idx = 0;
+
+        while idx < cmd_slice.len() {
+            match &cmd_slice[idx] {
+                Commands::Incr => {
+                    let run = run_length(&cmd_slice[idx..], &Commands::Incr);
+                    for chunk in count_chunks(run) {
+                        tokens.push(Token::Increment(chunk));
+                    }
+                    idx += run;
+                }
+                Commands::Decr => {
+                    let run = run_length(&cmd_slice[idx..], &Commands::Decr);
+                    for chunk in count_chunks(run) {
+                        tokens.push(Token::Decrement(chunk));
+                    }
+                    idx += run;
+                }
+                Commands::PtrRight => {
+                    let run = run_length(&cmd_slice[idx..], &Commands::PtrRight);
+                    tokens.push(Token::PointerRight(run));
+                    idx += run;
+                }
+                Commands::PtrLeft => {
+                    let run = run_length(&cmd_slice[idx..], &Commands::PtrLeft);
+                    tokens.push(Token::PointerLeft(run));
+                    idx += run;
+                }
+                Commands::Outp => {
+                    tokens.push(Token::Output);
+                    idx += 1;
+                }
+                Commands::Inpt => {
+                    tokens.push(Token::Input);
+                    idx += 1;
+                }
+                Commands::IfZero => {
+                    let (body, consumed) = Self::divide_cmd_slice(&cmd_slice[idx + 1..]);
+                    tokens.extend(optimize_loop(body));
+                    // Skip the `[`, the body and the closing `]`.
+                    idx += 1 + consumed + 1;
+                }
+                Commands::JmpNzero => {
+                    // End of the current block; hand control back to the caller.
+                    return (tokens, idx);
+                }
+            }
         }
+
+        (tokens, idx)
     }
+}
 
-    /// Creates a token sequence with a pre-allocated amount of memory.
-    pub fn with_capacity(capacity: usize) -> Self {
-        Self {
-            tokens: Vec::with_capacity(capacity),
+/// Counts how many times `cmd` appears consecutively at the front of `slice`.
+fn run_length(slice: &[Commands], cmd: &Commands) -> usize {
+    slice.iter().take_while(|c| *c == cmd).count()
+}
+
+/// Splits a run of `total` identical cell operations into `CellType`-sized
+/// chunks so a run of 256 or more does not narrow to a smaller (or zero) count.
+fn count_chunks(total: usize) -> impl Iterator<Item = CellType> {
+    let max = CellType::MAX as usize;
+    (0..)
+        .map(move |i| total.saturating_sub(i * max).min(max))
+        .take_while(|chunk| *chunk > 0)
+        .map(|chunk| chunk as CellType)
+}
+
+/// Applies loop-level peephole optimisations to a parsed loop body.
+///
+/// Recognises clear loops (`[-]`/`[+]`) as a [`Token::SetZero`] and
+/// multiply/copy loops as a run of [`Token::MultiplyAdd`] followed by a
+/// `SetZero`. Anything that does not fit either shape is returned unchanged as
+/// a single [`Token::IfZeroBlock`].
+fn optimize_loop(body: Vec<Token>) -> Vec<Token> {
+    // Clear loop: the body does nothing but step the current cell towards 0.
+    if body.len() == 1 && matches!(body[0], Token::Increment(1) | Token::Decrement(1)) {
+        return vec![Token::SetZero];
+    }
+
+    // Multiply/copy loop: only arithmetic and pointer moves, with the pointer
+    // returning to where it started each iteration.
+    let mut offset: isize = 0;
+    let mut deltas: BTreeMap<isize, i32> = BTreeMap::new();
+    for token in &body {
+        match token {
+            Token::Increment(n) => *deltas.entry(offset).or_insert(0) += *n as i32,
+            Token::Decrement(n) => *deltas.entry(offset).or_insert(0) -= *n as i32,
+            Token::PointerRight(n) => offset += *n as isize,
+            Token::PointerLeft(n) => offset -= *n as isize,
+            _ => return vec![Token::IfZeroBlock(body)],
+        }
+    }
+
+    // The control cell must be decremented by exactly one per iteration and
+    // carry no other net change, otherwise the closed form does not hold.
+    if offset != 0 || deltas.get(&0).copied() != Some(-1) {
+        return vec![Token::IfZeroBlock(body)];
+    }
+
+    let mut optimized = Vec::new();
+    for (off, delta) in deltas {
+        if off == 0 || delta == 0 {
+            continue;
+        }
+        optimized.push(Token::MultiplyAdd {
+            offset: off,
+            factor: delta,
+        });
+    }
+    optimized.push(Token::SetZero);
+    optimized
+}
+
+impl From<Vec<Commands>> for TokenSequence {
+    fn from(cmds: Vec<Commands>) -> Self {
+        let (tokens, _) = Self::divide_cmd_slice(cmds.as_slice());
+        Self { tokens }
+    }
+}
+
+/// An error produced while parsing BrainFuck source, carrying the byte offset
+/// of the offending bracket.
+#[derive(Debug, PartialEq)]
+pub enum ParseError {
+    /// A `[` was never closed.
+    UnmatchedOpen(usize),
+    /// A `]` had no matching `[`.
+    UnmatchedClose(usize),
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::UnmatchedOpen(offset) => {
+                write!(f, "unmatched '[' at byte offset {offset}")
+            }
+            ParseError::UnmatchedClose(offset) => {
+                write!(f, "unmatched ']' at byte offset {offset}")
+            }
+        }
+    }
+}
+
+/// Validates that every `[` has a matching `]`, reporting the byte offset of
+/// the first bracket that is left dangling.
+fn validate_brackets(source: &[u8]) -> Result<(), ParseError> {
+    let mut open: Vec<usize> = Vec::new();
+    for (offset, byte) in source.iter().enumerate() {
+        match byte {
+            b'[' => open.push(offset),
+            b']' if open.pop().is_none() => return Err(ParseError::UnmatchedClose(offset)),
+            _ => {}
+        }
+    }
+    match open.first() {
+        Some(offset) => Err(ParseError::UnmatchedOpen(*offset)),
+        None => Ok(()),
+    }
+}
+
+impl TokenSequence {
+    /// Parses textual source into a token sequence, reporting the byte offset of
+    /// any unbalanced bracket before folding runs into the counted tokens.
+    pub fn parse(source: &[u8]) -> Result<Self, ParseError> {
+        validate_brackets(source)?;
+        Ok(Self::from(lex(source)))
+    }
+
+    /// Flattens the nested token tree into a single instruction vector where
+    /// every loop is a [`Token::JumpIfZero`]/[`Token::JumpIfNotZero`] pair whose
+    /// `target` is the absolute index of its matching bracket, resolved here via
+    /// a stack of open-bracket indices so execution never recurses.
+    pub fn into_flat(self) -> Vec<Token> {
+        let mut out = Vec::with_capacity(self.tokens.len());
+        flatten_into(self.tokens, &mut out);
+        out
+    }
+}
+
+/// Appends `tokens` to `out`, rewriting `IfZeroBlock`s into jump pairs and
+/// back-patching each `JumpIfZero` once its matching `]` index is known.
+fn flatten_into(tokens: Vec<Token>, out: &mut Vec<Token>) {
+    for token in tokens {
+        match token {
+            Token::IfZeroBlock(body) => {
+                let open = out.len();
+                out.push(Token::JumpIfZero { target: 0 });
+                flatten_into(body, out);
+                let close = out.len();
+                out.push(Token::JumpIfNotZero { target: open });
+                out[open] = Token::JumpIfZero { target: close };
+            }
+            other => out.push(other),
         }
     }
+}
+
+/// Magic bytes prefixing a compiled `.bfc` artifact.
+const BYTECODE_MAGIC: &[u8; 4] = b"BFC\x01";
+/// Version of the bytecode format this build reads and writes.
+const BYTECODE_VERSION: u8 = 1;
+
+// One opcode byte per `Token` variant. Loops bracket their body with
+// `OP_OPEN_BLOCK`/`OP_CLOSE_BLOCK`.
+const OP_INCREMENT: u8 = 0x01;
+const OP_DECREMENT: u8 = 0x02;
+const OP_POINTER_RIGHT: u8 = 0x03;
+const OP_POINTER_LEFT: u8 = 0x04;
+const OP_OUTPUT: u8 = 0x05;
+const OP_INPUT: u8 = 0x06;
+const OP_SET_ZERO: u8 = 0x07;
+const OP_MULTIPLY_ADD: u8 = 0x08;
+const OP_OPEN_BLOCK: u8 = 0x09;
+const OP_CLOSE_BLOCK: u8 = 0x0A;
+
+impl TokenSequence {
+    /// Serialises the sequence to the compact `.bfc` bytecode format: a small
+    /// header followed by one record per token.
+    pub fn write_bytecode<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(BYTECODE_MAGIC)?;
+        writer.write_all(&[BYTECODE_VERSION, std::mem::size_of::<CellType>() as u8])?;
+        write_varint(writer, DEFAULT_TAPE_SIZE as u64)?;
+        write_tokens(writer, &self.tokens)
+    }
+
+    /// Loads a sequence previously written with [`TokenSequence::write_bytecode`],
+    /// validating the header and reconstructing nested loops recursively.
+    pub fn read_bytecode<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let mut header = [0u8; 6];
+        reader.read_exact(&mut header)?;
+        if &header[..4] != BYTECODE_MAGIC {
+            return Err(invalid_data("not a bfc bytecode stream"));
+        }
+        if header[4] != BYTECODE_VERSION {
+            return Err(invalid_data("unsupported bytecode version"));
+        }
+        if header[5] as usize != std::mem::size_of::<CellType>() {
+            return Err(invalid_data("bytecode cell width mismatch"));
+        }
+        // Default tape size; currently advisory, so simply consume it.
+        read_varint(reader)?;
 
-    /// Parses a vector of commands into execution blocks of commands.
-    fn divide_cmd_slice(&self, cmd_slice: &[Commands]) -> Vec<ParsingValue> {
-        let mut ifz_level = 0_usize;
-        let mut ifz_start = 0_usize;
-        let mut ifz_started = false;
-        let res: Vec<ParsingValue> = Vec::new();
+        let mut tokens = Vec::new();
+        while let Some(token) = parse_token(reader)? {
+            tokens.push(token);
+        }
+        Ok(Self { tokens })
+    }
+}
 
-        // Divide them into execution blocks.
-        for (idx, cmd) in cmd_slice.iter().enumerate() {
-            // Parse code as normal...
-            if !ifz_started && cmd.not_block() {
-                res.push(ParsingValue::Command(cmd as Commands));
+/// Writes a flat run of tokens, recursing into loop bodies.
+fn write_tokens<W: Write>(writer: &mut W, tokens: &[Token]) -> io::Result<()> {
+    for token in tokens {
+        match token {
+            Token::Increment(n) => {
+                writer.write_all(&[OP_INCREMENT])?;
+                write_varint(writer, *n as u64)?;
+            }
+            Token::Decrement(n) => {
+                writer.write_all(&[OP_DECREMENT])?;
+                write_varint(writer, *n as u64)?;
+            }
+            Token::PointerRight(n) => {
+                writer.write_all(&[OP_POINTER_RIGHT])?;
+                write_varint(writer, *n as u64)?;
+            }
+            Token::PointerLeft(n) => {
+                writer.write_all(&[OP_POINTER_LEFT])?;
+                write_varint(writer, *n as u64)?;
             }
-            else if cmd == &Commands::IF_ZERO {
-                ifz_level += 1;
-                ifz_started = true;
+            Token::Output => writer.write_all(&[OP_OUTPUT])?,
+            Token::Input => writer.write_all(&[OP_INPUT])?,
+            Token::SetZero => writer.write_all(&[OP_SET_ZERO])?,
+            Token::MultiplyAdd { offset, factor } => {
+                writer.write_all(&[OP_MULTIPLY_ADD])?;
+                write_signed_varint(writer, *offset as i64)?;
+                write_signed_varint(writer, *factor as i64)?;
             }
-            else if cmd == &Commands::JMP_NZERO {
-                ifz_level -= 0;
+            Token::IfZeroBlock(body) => {
+                writer.write_all(&[OP_OPEN_BLOCK])?;
+                write_tokens(writer, body)?;
+                writer.write_all(&[OP_CLOSE_BLOCK])?;
             }
+            // The bytecode mirrors the nested tree; flattening happens only
+            // afterwards, so jump instructions never reach serialisation.
+            Token::JumpIfZero { .. } | Token::JumpIfNotZero { .. } => {
+                unreachable!("bytecode is serialised before flattening")
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Reads a single token record, dispatching on the leading opcode byte.
+///
+/// Returns `Ok(None)` at end-of-stream or when the close-block opcode ends the
+/// current body, and errors on an unknown opcode.
+fn parse_token<R: Read>(reader: &mut R) -> io::Result<Option<Token>> {
+    let opcode = match read_u8(reader)? {
+        Some(opcode) => opcode,
+        None => return Ok(None),
+    };
 
-            // We closed it, parse 
-            if ifz_started && ifz_level == 0 {
-                ifz_started = false;
-                res.push(ParsingValue::Block(self.divide_cmd_slice(cmd_slice[ifz_start + 1..idx].into())));
+    let token = match opcode {
+        OP_INCREMENT => Token::Increment(read_varint_required(reader)? as CellType),
+        OP_DECREMENT => Token::Decrement(read_varint_required(reader)? as CellType),
+        OP_POINTER_RIGHT => Token::PointerRight(read_varint_required(reader)? as usize),
+        OP_POINTER_LEFT => Token::PointerLeft(read_varint_required(reader)? as usize),
+        OP_OUTPUT => Token::Output,
+        OP_INPUT => Token::Input,
+        OP_SET_ZERO => Token::SetZero,
+        OP_MULTIPLY_ADD => {
+            let offset = read_signed_varint(reader)? as isize;
+            let factor = read_signed_varint(reader)? as i32;
+            Token::MultiplyAdd { offset, factor }
+        }
+        OP_OPEN_BLOCK => {
+            let mut body = Vec::new();
+            while let Some(token) = parse_token(reader)? {
+                body.push(token);
             }
+            Token::IfZeroBlock(body)
         }
+        OP_CLOSE_BLOCK => return Ok(None),
+        other => return Err(invalid_data(&format!("unknown opcode {other:#04x}"))),
+    };
+    Ok(Some(token))
+}
 
-        res
+/// Writes an unsigned integer as LEB128.
+fn write_varint<W: Write>(writer: &mut W, mut value: u64) -> io::Result<()> {
+    loop {
+        let mut byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value != 0 {
+            byte |= 0x80;
+        }
+        writer.write_all(&[byte])?;
+        if value == 0 {
+            return Ok(());
+        }
     }
 }
 
+/// Writes a signed integer as zig-zag encoded LEB128.
+fn write_signed_varint<W: Write>(writer: &mut W, value: i64) -> io::Result<()> {
+    write_varint(writer, ((value << 1) ^ (value >> 63)) as u64)
+}
 
-impl From<Vec<Commands>> for TokenSequence {
-    fn from(cmds: Vec<Commands>) -> Self {
-        // Alloc a vector of approx half of the len of cmds.
-        let seq = Self::with_capacity(cmds.len() / 2);
-        //seq.parse_command_seq(cmds.as_slice());
-        seq
+/// Reads a single byte, returning `None` at end-of-stream.
+fn read_u8<R: Read>(reader: &mut R) -> io::Result<Option<u8>> {
+    let mut byte = [0u8; 1];
+    match reader.read(&mut byte)? {
+        0 => Ok(None),
+        _ => Ok(Some(byte[0])),
     }
 }
 
+/// Reads an unsigned LEB128 integer, returning `None` at a clean EOF.
+fn read_varint<R: Read>(reader: &mut R) -> io::Result<Option<u64>> {
+    let mut value = 0u64;
+    let mut shift = 0u32;
+    let mut first = true;
+    loop {
+        let byte = match read_u8(reader)? {
+            Some(byte) => byte,
+            None if first => return Ok(None),
+            None => return Err(invalid_data("truncated varint")),
+        };
+        first = false;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(Some(value));
+        }
+        shift += 7;
+    }
+}
+
+/// Reads an unsigned LEB128 integer, erroring on a truncated stream.
+fn read_varint_required<R: Read>(reader: &mut R) -> io::Result<u64> {
+    read_varint(reader)?.ok_or_else(|| invalid_data("unexpected end of bytecode"))
+}
+
+/// Reads a zig-zag encoded signed LEB128 integer.
+fn read_signed_varint<R: Read>(reader: &mut R) -> io::Result<i64> {
+    let raw = read_varint_required(reader)?;
+    Ok(((raw >> 1) as i64) ^ -((raw & 1) as i64))
+}
+
+/// Shorthand for building an `InvalidData` I/O error.
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Reads BrainFuck source bytes into a vector of [`Commands`], discarding any
+/// characters that are not part of the language.
+fn lex(source: &[u8]) -> Vec<Commands> {
+    source
+        .iter()
+        .filter_map(|b| Commands::try_from(*b).ok())
+        .collect()
+}
+
 /// The main structure for executing brainfuck code. Handles and manages the operations
 /// of the BrainFuck program being executed.
 struct BrainFuckExecutor {
-    ptr: CellPtrType,
-    cells: [CellType; CellType::MAX as usize]
+    ptr: usize,
+    cells: Vec<CellType>,
+    /// When `true`, the tape grows on demand instead of being fixed in size.
+    grow: bool,
+    /// Opt-in wrapping behaviours.
+    features: Features,
+}
+
+impl BrainFuckExecutor {
+    /// Creates an executor with a zeroed tape of `tape_size` cells.
+    pub fn with_tape_size(tape_size: usize, grow: bool, features: Features) -> Self {
+        Self {
+            ptr: 0,
+            cells: vec![0; tape_size],
+            grow,
+            features,
+        }
+    }
+
+    /// Zeroes the tape and returns the pointer to the origin.
+    pub fn reset(&mut self) {
+        self.ptr = 0;
+        for cell in self.cells.iter_mut() {
+            *cell = 0;
+        }
+    }
+
+    /// Ensures the cell under the pointer exists, growing the tape when the
+    /// `grow` mode is enabled.
+    fn ensure_capacity(&mut self) {
+        if self.grow && self.ptr >= self.cells.len() {
+            self.cells.resize(self.ptr + 1, 0);
+        }
+    }
+
+    /// Moves the pointer `n` cells to the right, honouring the `grow` and
+    /// `pointer-wrap` settings.
+    fn move_right(&mut self, n: usize) -> Result<(), RuntimeError> {
+        self.ptr += n;
+        self.ensure_capacity();
+        if self.ptr >= self.cells.len() {
+            if !self.features.pointer_wrap {
+                return Err(RuntimeError::PointerOverflow);
+            }
+            self.ptr %= self.cells.len();
+        }
+        Ok(())
+    }
+
+    /// Resolves `ptr + offset` to a concrete cell index, honouring `grow` and
+    /// `pointer-wrap` exactly as the pointer-move instructions do. Used by
+    /// [`Token::MultiplyAdd`], whose offset may reach outside the tape.
+    fn offset_target(&mut self, offset: isize) -> Result<usize, RuntimeError> {
+        let target = self.ptr as isize + offset;
+        if target < 0 {
+            if self.features.pointer_wrap {
+                let len = self.cells.len() as isize;
+                Ok((target.rem_euclid(len)) as usize)
+            } else {
+                Err(RuntimeError::PointerUnderflow)
+            }
+        } else if target as usize >= self.cells.len() {
+            if self.grow {
+                self.cells.resize(target as usize + 1, 0);
+                Ok(target as usize)
+            } else if self.features.pointer_wrap {
+                Ok(target as usize % self.cells.len())
+            } else {
+                Err(RuntimeError::PointerOverflow)
+            }
+        } else {
+            Ok(target as usize)
+        }
+    }
+
+    /// Moves the pointer `n` cells to the left, honouring the `pointer-wrap`
+    /// setting.
+    fn move_left(&mut self, n: usize) -> Result<(), RuntimeError> {
+        if n > self.ptr {
+            if self.features.pointer_wrap {
+                let len = self.cells.len();
+                self.ptr = (self.ptr + len - (n % len)) % len;
+            } else {
+                return Err(RuntimeError::PointerUnderflow);
+            }
+        } else {
+            self.ptr -= n;
+        }
+        Ok(())
+    }
+
+    /// Executes a flattened program against the tape.
+    ///
+    /// The program must have been produced by [`TokenSequence::into_flat`], so
+    /// loops are jump pairs and this is a single tight loop over a program
+    /// counter with no recursion or per-loop allocation.
+    pub fn run(&mut self, program: &[Token]) -> Result<(), RuntimeError> {
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
+        let mut pc = 0;
+
+        while let Some(token) = program.get(pc) {
+            match token {
+                Token::Increment(n) => {
+                    let cell = &mut self.cells[self.ptr];
+                    if self.features.cell_wrap {
+                        *cell = cell.wrapping_add(*n);
+                    } else {
+                        *cell = cell.checked_add(*n).ok_or(RuntimeError::CellOverflow)?;
+                    }
+                }
+                Token::Decrement(n) => {
+                    let cell = &mut self.cells[self.ptr];
+                    if self.features.cell_wrap {
+                        *cell = cell.wrapping_sub(*n);
+                    } else {
+                        *cell = cell.checked_sub(*n).ok_or(RuntimeError::CellOverflow)?;
+                    }
+                }
+                Token::PointerRight(n) => self.move_right(*n)?,
+                Token::PointerLeft(n) => self.move_left(*n)?,
+                Token::Output => out.write_all(&[self.cells[self.ptr]])?,
+                Token::Input => {
+                    let mut buf = [0u8; 1];
+                    match io::stdin().read(&mut buf)? {
+                        0 => self.cells[self.ptr] = 0,
+                        _ => self.cells[self.ptr] = buf[0],
+                    }
+                }
+                Token::SetZero => self.cells[self.ptr] = 0,
+                Token::MultiplyAdd { offset, factor } => {
+                    let value = self.cells[self.ptr];
+                    if value != 0 {
+                        let target = self.offset_target(*offset)?;
+                        let current = self.cells[target];
+                        // `factor` is signed: compute `current + value * factor`
+                        // and either wrap it into a byte or require it to stay
+                        // in range, mirroring the checked `Increment`/`Decrement`.
+                        let product = value as i32 * factor;
+                        self.cells[target] = if self.features.cell_wrap {
+                            current.wrapping_add(product as CellType)
+                        } else {
+                            let result = current as i32 + product;
+                            if result < 0 || result > CellType::MAX as i32 {
+                                return Err(RuntimeError::CellOverflow);
+                            }
+                            result as CellType
+                        };
+                    }
+                }
+                Token::JumpIfZero { target } => {
+                    if self.cells[self.ptr] == 0 {
+                        pc = target + 1;
+                        continue;
+                    }
+                }
+                Token::JumpIfNotZero { target } => {
+                    if self.cells[self.ptr] != 0 {
+                        pc = target + 1;
+                        continue;
+                    }
+                }
+                Token::IfZeroBlock(_) => {
+                    unreachable!("program must be flattened before execution")
+                }
+            }
+            pc += 1;
+        }
+        Ok(())
+    }
+}
+
+/// Computes the net bracket depth of a command slice: positive for unmatched
+/// `[`, zero when balanced and negative once an unmatched `]` is seen.
+fn bracket_balance(cmds: &[Commands]) -> i64 {
+    let mut depth = 0;
+    for cmd in cmds {
+        match cmd {
+            Commands::IfZero => depth += 1,
+            Commands::JmpNzero => {
+                depth -= 1;
+                if depth < 0 {
+                    return depth;
+                }
+            }
+            _ => {}
+        }
+    }
+    depth
+}
+
+/// Runs an interactive read-eval-print loop against a persistent executor.
+///
+/// Lines that leave a `[` open are buffered until the matching `]` arrives, so
+/// multi-line loops can be entered incrementally. Meta-commands prefixed with
+/// `:` operate on the executor state directly.
+fn repl(executor: &mut BrainFuckExecutor) -> io::Result<()> {
+    let stdin = io::stdin();
+    let mut buffer: Vec<Commands> = Vec::new();
+    let mut line = String::new();
+
+    loop {
+        let prompt = if buffer.is_empty() { "bf> " } else { "... " };
+        print!("{prompt}");
+        io::stdout().flush()?;
+
+        line.clear();
+        if stdin.read_line(&mut line)? == 0 {
+            println!();
+            break;
+        }
+        let trimmed = line.trim();
+
+        // Meta-commands are only recognised outside of an open block.
+        if buffer.is_empty() && trimmed.starts_with(':') {
+            match run_meta(executor, trimmed) {
+                Ok(true) => break,
+                Ok(false) => {}
+                Err(msg) => eprintln!("{msg}"),
+            }
+            continue;
+        }
+
+        buffer.extend(lex(line.as_bytes()));
+        match bracket_balance(&buffer) {
+            depth if depth > 0 => continue,
+            depth if depth < 0 => {
+                eprintln!("unmatched ']'");
+                buffer.clear();
+                continue;
+            }
+            _ => {}
+        }
+
+        let program = TokenSequence::from(std::mem::take(&mut buffer)).into_flat();
+        if let Err(err) = executor.run(&program) {
+            eprintln!("execution error: {err}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Handles a single `:` meta-command. Returns `Ok(true)` when the REPL should
+/// exit, `Ok(false)` to continue and `Err` with a message on bad input.
+fn run_meta(executor: &mut BrainFuckExecutor, cmd: &str) -> Result<bool, String> {
+    let mut parts = cmd.split_whitespace();
+    match parts.next() {
+        Some(":quit") | Some(":exit") => return Ok(true),
+        Some(":reset") => executor.reset(),
+        Some(":ptr") => {
+            let value = executor.cells.get(executor.ptr).copied().unwrap_or(0);
+            println!("ptr = {}, cell = {}", executor.ptr, value);
+        }
+        Some(":dump") => {
+            let count: usize = match parts.next() {
+                Some(arg) => arg.parse().map_err(|_| format!("invalid cell count: {arg}"))?,
+                None => return Err(":dump requires a cell count".to_string()),
+            };
+            let end = count.min(executor.cells.len());
+            println!("{:?}", &executor.cells[..end]);
+        }
+        Some(other) => return Err(format!("unknown meta-command: {other}")),
+        None => {}
+    }
+    Ok(false)
+}
+
+/// A BrainFuck interpreter.
+#[derive(Parser)]
+#[command(name = "brainf", about = "A BrainFuck interpreter.")]
+struct Args {
+    /// Path to the BrainFuck source file to execute. Omit to enter the REPL.
+    source: Option<PathBuf>,
+
+    /// Number of cells in the tape.
+    #[arg(short = 'a', long = "array-size", default_value_t = DEFAULT_TAPE_SIZE)]
+    array_size: usize,
+
+    /// Grow the tape on demand when the pointer moves past the end.
+    #[arg(long)]
+    grow: bool,
+
+    /// Opt-in execution features, e.g. `cell-wrap`, `pointer-wrap`.
+    #[arg(long, value_delimiter = ',')]
+    features: Vec<String>,
+
+    /// Compile the source to a `.bfc` bytecode file at this path instead of
+    /// executing it.
+    #[arg(short = 'c', long = "compile")]
+    compile: Option<PathBuf>,
+}
+
+/// Compiles a parsed sequence to a bytecode file.
+fn compile_to_file(sequence: &TokenSequence, path: &PathBuf) -> Result<(), String> {
+    let file = fs::File::create(path).map_err(|err| err.to_string())?;
+    let mut writer = io::BufWriter::new(file);
+    sequence
+        .write_bytecode(&mut writer)
+        .and_then(|()| writer.flush())
+        .map_err(|err| err.to_string())
 }
 
 fn main() {
-    println!("Hello, world!");
+    let args = Args::parse();
+
+    let features = match Features::from_names(&args.features) {
+        Ok(features) => features,
+        Err(name) => {
+            eprintln!("unknown feature: {name}");
+            std::process::exit(1);
+        }
+    };
+
+    let mut executor = BrainFuckExecutor::with_tape_size(args.array_size, args.grow, features);
+
+    let result = match &args.source {
+        Some(path) => {
+            let bytes = match fs::read(path) {
+                Ok(bytes) => bytes,
+                Err(err) => {
+                    eprintln!("failed to read {}: {err}", path.display());
+                    std::process::exit(1);
+                }
+            };
+
+            // A file carrying the magic bytes is already-compiled bytecode;
+            // anything else is treated as textual source.
+            let sequence = if bytes.starts_with(BYTECODE_MAGIC) {
+                match TokenSequence::read_bytecode(&mut bytes.as_slice()) {
+                    Ok(sequence) => sequence,
+                    Err(err) => {
+                        eprintln!("failed to load bytecode: {err}");
+                        std::process::exit(1);
+                    }
+                }
+            } else {
+                match TokenSequence::parse(&bytes) {
+                    Ok(sequence) => sequence,
+                    Err(err) => {
+                        eprintln!("parse error: {err}");
+                        std::process::exit(1);
+                    }
+                }
+            };
+
+            if let Some(out_path) = &args.compile {
+                compile_to_file(&sequence, out_path)
+            } else {
+                executor
+                    .run(&sequence.into_flat())
+                    .map_err(|err| err.to_string())
+            }
+        }
+        None => repl(&mut executor).map_err(|err| err.to_string()),
+    };
+
+    if let Err(err) = result {
+        eprintln!("execution error: {err}");
+        std::process::exit(1);
+    }
 }